@@ -1,6 +1,10 @@
 use nom::lib::std::fmt::Formatter;
 use core::fmt;
 
+use std::collections::HashMap;
+
+use crate::grid::{Grid, Neighborhood, Position, Rule, SparseGrid};
+
 #[derive(Clone, PartialEq)]
 pub enum Seat {
     Floor,
@@ -10,25 +14,6 @@ pub enum Seat {
 
 type Layout = Vec<Vec<Seat>>;
 
-pub struct State {
-    seats: Layout,
-    height: usize,
-    width: usize,
-    changes: usize,
-}
-
-#[derive(Copy, Clone)]
-pub struct Position {
-    x: isize,
-    y: isize,
-}
-
-#[derive(Copy, Clone, PartialEq)]
-pub enum Depth {
-    Inf,
-    Next,
-}
-
 impl fmt::Debug for Seat {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match *self {
@@ -50,87 +35,68 @@ impl Seat {
     }
 }
 
-impl State {
-    fn new(seats: Layout) -> State {
-        let height = seats.len();
-        let width = seats[0].len();
-
-        State {
-            seats,
-            height,
-            width,
-            changes: 0,
-        }
+/// The day 11 seating automaton as a `Rule`: a seat fills up once it sees no
+/// occupied neighbors, and empties out once it sees at least `threshold`.
+pub struct SeatRule {
+    threshold: usize,
+}
+
+impl Rule for SeatRule {
+    type Cell = Seat;
+
+    fn passable(&self, cell: &Seat) -> bool {
+        *cell == Seat::Floor
+    }
+
+    fn is_active(&self, cell: &Seat) -> bool {
+        *cell == Seat::Occupied
     }
 
-    fn next_seat(&self, pos: Position, depth: Depth) -> Seat {
-        let width = self.width as isize;
-        let height = self.height as isize;
-        let in_bounds = |x, y| x >= 0 && x < width && y >= 0 && y < height;
-        let dirs = [
-            (-1, -1), (-1, 0), (-1, 1),
-            (0, -1), (0, 1),
-            (1, -1), (1, 0), (1, 1)
-        ];
-
-        let count = dirs
-            .iter()
-            .fold(0, |acc, (xdir, ydir)| {
-                let mut p = Position { x: pos.x + xdir, y: pos.y + ydir };
-                while depth == Depth::Inf && in_bounds(p.x, p.y) &&
-                    self.seats[p.y as usize][p.x as usize] == Seat::Floor {
-                    p = Position { x: p.x + xdir, y: p.y + ydir };
-                }
-
-                if in_bounds(p.x, p.y) &&
-                    self.seats[p.y as usize][p.x as usize] == Seat::Occupied {
-                    acc + 1
-                } else {
-                    acc
-                }
-            });
-
-        let cond = match depth {
-            Depth::Inf => 5,
-            Depth::Next => 4
-        };
-        match &self.seats[pos.y as usize][pos.x as usize] {
-            Seat::Empty if count == 0 => Seat::Occupied,
-            Seat::Occupied if count >= cond => Seat::Empty,
+    fn next(&self, cell: &Seat, active_neighbors: usize) -> Seat {
+        match cell {
+            Seat::Empty if active_neighbors == 0 => Seat::Occupied,
+            Seat::Occupied if active_neighbors >= self.threshold => Seat::Empty,
             seat => seat.clone()
         }
     }
+}
 
-    fn update(&self, depth: Depth) -> State {
-        let mut seats: Layout = self.seats.to_vec();
-        let changes =
-            (0..self.height).fold(0, |acc, y|
-                (0..self.width).fold(0, |acc, x| {
-                    let p = Position { x: x as isize, y: y as isize };
-                    let seat = self.next_seat(p, depth);
-                    let change = if seat != seats[y][x] { 1 } else { 0 };
-                    seats[y][x] = seat;
-                    acc + change
-                }) + acc,
-            );
-
-        State {
-            seats,
-            changes,
-            ..*self
-        }
+pub struct State {
+    grid: Grid<Seat>,
+    rule: SeatRule,
+}
+
+impl State {
+    fn new(seats: Layout, neighborhood: Neighborhood, threshold: usize) -> State {
+        let rule = SeatRule { threshold };
+        let grid = Grid::new(seats, neighborhood, &rule);
+        State { grid, rule }
     }
 
-    fn count_occupied(&self) -> usize {
-        self.seats.iter().fold(0, |acc, x|
-            acc + x.iter().fold(0, |acc, y|
-                acc + if *y == Seat::Occupied { 1 } else { 0 }))
+    fn step(&mut self) -> usize {
+        self.grid.step(&self.rule)
+    }
+
+    fn step_until_stable(&mut self) -> (usize, usize) {
+        self.grid.step_until_stable(&self.rule)
+    }
+
+    // Renders the grid using the same glyphs as `Debug for Seat`, plus a
+    // summary line, so a generation can be eyeballed without reaching for
+    // `println!` in a test.
+    pub fn render(&self) -> String {
+        let grid = self.grid.cells().iter()
+            .map(|row| row.iter().map(|seat| format!("{:?}", seat)).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        format!("{}\nchanges: {}, occupied: {}", grid, self.grid.changes(), self.grid.count_active(&self.rule))
     }
 }
 
 #[aoc_generator(day11)]
-pub fn gen(input: &str) -> State {
-    let seats: Layout = input
+pub fn gen(input: &str) -> Layout {
+    input
         .lines()
         .map(|line| line
             .trim()
@@ -138,28 +104,72 @@ pub fn gen(input: &str) -> State {
             .map(Seat::from)
             .collect()
         )
+        .collect()
+}
+
+#[aoc(day11, part1)]
+pub fn solve_part1(seats: &Layout) -> usize {
+    State::new(seats.clone(), Neighborhood::Adjacent, 4).step_until_stable().1
+}
+
+#[aoc(day11, part2)]
+pub fn solve_part2(seats: &Layout) -> usize {
+    State::new(seats.clone(), Neighborhood::LineOfSight, 5).step_until_stable().1
+}
+
+/// A sparse alternative to `Layout`: only occupied/empty seats are stored,
+/// so a large but mostly-floor input doesn't pay the full `width * height`
+/// dense-vector cost.
+pub struct SparseLayout {
+    seats: HashMap<Position, Seat>,
+    width: usize,
+    height: usize,
+}
+
+#[aoc_generator(day11, sparse)]
+pub fn gen_sparse(input: &str) -> SparseLayout {
+    let rows: Vec<&str> = input.lines().map(str::trim).collect();
+    let height = rows.len();
+    let width = rows[0].len();
+
+    let seats = rows.iter().enumerate()
+        .flat_map(|(y, row)| row.chars().enumerate().map(move |(x, c)| (x, y, c)))
+        .filter_map(|(x, y, c)| match Seat::from(c) {
+            Seat::Floor => None,
+            seat => Some((Position { x: x as isize, y: y as isize }, seat)),
+        })
         .collect();
 
-    State::new(seats)
+    SparseLayout { seats, width, height }
 }
 
-fn rec(state: &State, depth: Depth) -> usize {
-    let state = state.update(depth);
-    if state.changes == 0 {
-        state.count_occupied()
-    } else {
-        rec(&state, depth)
-    }
+#[aoc(day11, part1, sparse)]
+pub fn solve_part1_sparse(layout: &SparseLayout) -> usize {
+    let rule = SeatRule { threshold: 4 };
+    let mut grid = SparseGrid::new(layout.seats.clone(), Seat::Floor, layout.width, layout.height, Neighborhood::Adjacent, &rule);
+    grid.step_until_stable(&rule).1
 }
 
-#[aoc(day11, part1)]
-pub fn solve_part1(state: &State) -> usize {
-    rec(state, Depth::Next)
+#[aoc(day11, part2, sparse)]
+pub fn solve_part2_sparse(layout: &SparseLayout) -> usize {
+    let rule = SeatRule { threshold: 5 };
+    let mut grid = SparseGrid::new(layout.seats.clone(), Seat::Floor, layout.width, layout.height, Neighborhood::LineOfSight, &rule);
+    grid.step_until_stable(&rule).1
 }
 
-#[aoc(day11, part2)]
-pub fn solve_part2(state: &State) -> usize {
-    rec(state, Depth::Inf)
+pub fn solve_trace(input: &str, neighborhood: Neighborhood, threshold: usize) -> Vec<String> {
+    let mut state = State::new(gen(input), neighborhood, threshold);
+    let mut frames = vec![state.render()];
+
+    loop {
+        let changes = state.step();
+        frames.push(state.render());
+        if changes == 0 {
+            break;
+        }
+    }
+
+    frames
 }
 
 #[cfg(test)]
@@ -182,7 +192,7 @@ L.LLLLL.LL";
     #[test]
     fn test_gen() {
         let s = gen(get_input());
-        for v in s.seats {
+        for v in s {
             println!("{:?}", v);
         }
     }
@@ -196,4 +206,16 @@ L.LLLLL.LL";
     fn test_part2() {
         assert_eq!(solve_part2(&gen(get_input())), 26);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_sparse_matches_dense() {
+        assert_eq!(solve_part1_sparse(&gen_sparse(get_input())), 37);
+        assert_eq!(solve_part2_sparse(&gen_sparse(get_input())), 26);
+    }
+
+    #[test]
+    fn test_solve_trace() {
+        let frames = solve_trace(get_input(), Neighborhood::Adjacent, 4);
+        assert_eq!(frames.last().unwrap().contains("changes: 0"), true);
+    }
+}
@@ -0,0 +1,281 @@
+//! Reusable 2D grid / cellular-automaton helpers: a dense `Grid<T>`, a
+//! sparse `SparseGrid<T>`, Moore neighbor offsets, and a pluggable `Rule` so
+//! day-specific logic only has to describe what a cell does, not how
+//! neighbors are found or cached.
+
+use std::collections::HashMap;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Position {
+    pub x: isize,
+    pub y: isize,
+}
+
+impl Position {
+    /// The 8 offsets of a Moore neighborhood (all cells touching a cell,
+    /// including diagonals).
+    pub fn moore() -> [(isize, isize); 8] {
+        [
+            (-1, -1), (-1, 0), (-1, 1),
+            (0, -1), (0, 1),
+            (1, -1), (1, 0), (1, 1),
+        ]
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Neighborhood {
+    /// Only the 8 immediately touching cells count.
+    Adjacent,
+    /// Walk each of the 8 directions until a non-passable cell is hit (or
+    /// the grid edge is reached); that cell counts as the neighbor.
+    LineOfSight,
+}
+
+/// The rules of a cellular automaton: which cells block/pass a line-of-sight
+/// ray, which cells count toward a neighbor's occupancy, and what a cell
+/// becomes given its current value and how many active neighbors it has.
+pub trait Rule {
+    type Cell: Clone + PartialEq;
+
+    /// Whether a `LineOfSight` ray continues through this cell.
+    fn passable(&self, _cell: &Self::Cell) -> bool {
+        false
+    }
+
+    /// Whether this cell counts toward a neighbor's active count.
+    fn is_active(&self, cell: &Self::Cell) -> bool;
+
+    /// The next value of `cell`, given how many of its neighbors are active.
+    fn next(&self, cell: &Self::Cell, active_neighbors: usize) -> Self::Cell;
+}
+
+pub struct Grid<T> {
+    cells: Vec<Vec<T>>,
+    scratch: Vec<Vec<T>>,
+    width: usize,
+    height: usize,
+    neighbors: Vec<Vec<usize>>,
+    changes: usize,
+}
+
+impl<T: Clone + PartialEq> Grid<T> {
+    pub fn new<R: Rule<Cell = T>>(cells: Vec<Vec<T>>, neighborhood: Neighborhood, rule: &R) -> Grid<T> {
+        let height = cells.len();
+        let width = cells[0].len();
+        let scratch = cells.clone();
+
+        let mut grid = Grid {
+            cells,
+            scratch,
+            width,
+            height,
+            neighbors: Vec::new(),
+            changes: 0,
+        };
+        grid.neighbors = grid.build_neighbors(neighborhood, rule);
+        grid
+    }
+
+    fn in_bounds(&self, x: isize, y: isize) -> bool {
+        x >= 0 && x < self.width as isize && y >= 0 && y < self.height as isize
+    }
+
+    // One-time scan, per cell per direction, recording the flat index of the
+    // neighbor that will count toward that cell's active-neighbor tally.
+    fn build_neighbors<R: Rule<Cell = T>>(&self, neighborhood: Neighborhood, rule: &R) -> Vec<Vec<usize>> {
+        (0..self.height).flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                Position::moore().iter()
+                    .filter_map(|(xdir, ydir)| {
+                        let mut px = x as isize + xdir;
+                        let mut py = y as isize + ydir;
+                        while neighborhood == Neighborhood::LineOfSight &&
+                            self.in_bounds(px, py) &&
+                            rule.passable(&self.cells[py as usize][px as usize]) {
+                            px += xdir;
+                            py += ydir;
+                        }
+
+                        if self.in_bounds(px, py) {
+                            Some(py as usize * self.width + px as usize)
+                        } else {
+                            None
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn active_neighbors<R: Rule<Cell = T>>(&self, idx: usize, rule: &R) -> usize {
+        self.neighbors[idx]
+            .iter()
+            .filter(|&&n| rule.is_active(&self.cells[n / self.width][n % self.width]))
+            .count()
+    }
+
+    /// Advances the grid by one generation, writing into the scratch buffer
+    /// and swapping it in. Returns the number of cells that changed.
+    pub fn step<R: Rule<Cell = T>>(&mut self, rule: &R) -> usize {
+        let changes =
+            (0..self.height).fold(0, |acc, y|
+                (0..self.width).fold(0, |acc, x| {
+                    let idx = y * self.width + x;
+                    let next = rule.next(&self.cells[y][x], self.active_neighbors(idx, rule));
+                    let change = if next != self.cells[y][x] { 1 } else { 0 };
+                    self.scratch[y][x] = next;
+                    acc + change
+                }) + acc,
+            );
+
+        std::mem::swap(&mut self.cells, &mut self.scratch);
+        self.changes = changes;
+        changes
+    }
+
+    /// Steps until a generation produces no changes, returning the number of
+    /// generations that changed at least one cell (the final, unchanged
+    /// generation is not counted) and the final active-cell count.
+    pub fn step_until_stable<R: Rule<Cell = T>>(&mut self, rule: &R) -> (usize, usize) {
+        let mut generations = 0;
+        while self.step(rule) != 0 {
+            generations += 1;
+        }
+        (generations, self.count_active(rule))
+    }
+
+    pub fn count_active<R: Rule<Cell = T>>(&self, rule: &R) -> usize {
+        self.cells.iter().fold(0, |acc, row|
+            acc + row.iter().fold(0, |acc, cell|
+                acc + if rule.is_active(cell) { 1 } else { 0 }))
+    }
+
+    pub fn changes(&self) -> usize {
+        self.changes
+    }
+
+    pub fn cells(&self) -> &Vec<Vec<T>> {
+        &self.cells
+    }
+}
+
+/// Same cellular-automaton stepping as `Grid`, but backed by a `HashMap` that
+/// only stores non-default cells. Worthwhile for large, mostly-floor inputs
+/// where the dense `width * height` allocation would be wasteful: neighbor
+/// lists and generation stepping only ever visit present cells.
+pub struct SparseGrid<T> {
+    cells: HashMap<Position, T>,
+    scratch: HashMap<Position, T>,
+    default: T,
+    width: usize,
+    height: usize,
+    neighbors: HashMap<Position, Vec<Position>>,
+    changes: usize,
+}
+
+impl<T: Clone + PartialEq> SparseGrid<T> {
+    pub fn new<R: Rule<Cell = T>>(
+        cells: HashMap<Position, T>,
+        default: T,
+        width: usize,
+        height: usize,
+        neighborhood: Neighborhood,
+        rule: &R,
+    ) -> SparseGrid<T> {
+        let scratch = cells.clone();
+
+        let mut grid = SparseGrid {
+            cells,
+            scratch,
+            default,
+            width,
+            height,
+            neighbors: HashMap::new(),
+            changes: 0,
+        };
+        grid.neighbors = grid.build_neighbors(neighborhood, rule);
+        grid
+    }
+
+    fn in_bounds(&self, pos: Position) -> bool {
+        pos.x >= 0 && pos.x < self.width as isize && pos.y >= 0 && pos.y < self.height as isize
+    }
+
+    fn get(&self, pos: Position) -> &T {
+        self.cells.get(&pos).unwrap_or(&self.default)
+    }
+
+    // Only present cells get a neighbor list: missing (default) cells never
+    // change, so there's nothing to precompute for them. A missing cell is
+    // only listed as a neighbor when the default value counts as active.
+    fn build_neighbors<R: Rule<Cell = T>>(&self, neighborhood: Neighborhood, rule: &R) -> HashMap<Position, Vec<Position>> {
+        self.cells.keys()
+            .map(|&pos| {
+                let found = Position::moore().iter()
+                    .filter_map(|(xdir, ydir)| {
+                        let mut p = Position { x: pos.x + xdir, y: pos.y + ydir };
+                        while neighborhood == Neighborhood::LineOfSight &&
+                            self.in_bounds(p) &&
+                            rule.passable(self.get(p)) {
+                            p = Position { x: p.x + xdir, y: p.y + ydir };
+                        }
+
+                        if self.in_bounds(p) && (self.cells.contains_key(&p) || rule.is_active(&self.default)) {
+                            Some(p)
+                        } else {
+                            None
+                        }
+                    })
+                    .collect();
+                (pos, found)
+            })
+            .collect()
+    }
+
+    fn active_neighbors<R: Rule<Cell = T>>(&self, pos: Position, rule: &R) -> usize {
+        self.neighbors[&pos]
+            .iter()
+            .filter(|&&n| rule.is_active(self.get(n)))
+            .count()
+    }
+
+    /// Advances the grid by one generation. Returns the number of present
+    /// cells that changed.
+    pub fn step<R: Rule<Cell = T>>(&mut self, rule: &R) -> usize {
+        let positions: Vec<Position> = self.cells.keys().copied().collect();
+        let changes = positions.into_iter().fold(0, |acc, pos| {
+            let next = rule.next(self.get(pos), self.active_neighbors(pos, rule));
+            let change = if next != self.cells[&pos] { 1 } else { 0 };
+            self.scratch.insert(pos, next);
+            acc + change
+        });
+
+        std::mem::swap(&mut self.cells, &mut self.scratch);
+        self.changes = changes;
+        changes
+    }
+
+    /// Steps until a generation produces no changes, returning the number of
+    /// generations that changed at least one cell (the final, unchanged
+    /// generation is not counted) and the final active-cell count.
+    pub fn step_until_stable<R: Rule<Cell = T>>(&mut self, rule: &R) -> (usize, usize) {
+        let mut generations = 0;
+        while self.step(rule) != 0 {
+            generations += 1;
+        }
+        (generations, self.count_active(rule))
+    }
+
+    pub fn count_active<R: Rule<Cell = T>>(&self, rule: &R) -> usize {
+        self.cells.values().filter(|cell| rule.is_active(cell)).count()
+    }
+
+    pub fn changes(&self) -> usize {
+        self.changes
+    }
+
+    pub fn cells(&self) -> &HashMap<Position, T> {
+        &self.cells
+    }
+}
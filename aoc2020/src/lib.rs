@@ -0,0 +1,6 @@
+use aoc_runner_derive::aoc_lib;
+
+pub mod grid;
+mod day11;
+
+aoc_lib! { year = 2020 }